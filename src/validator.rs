@@ -1,105 +1,1018 @@
 use matches::assert_matches;
+use serde_derive::Serialize;
 use serde_json::{Number, Value};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use url::Url;
+
+/// The reason a single keyword failed to validate an instance.
+#[derive(Debug)]
+pub enum ValidationErrorKind {
+    /// The instance did not equal the schema's `const` value.
+    Const { expected: Value },
+    /// The instance did not equal any of the schema's `enum` variants.
+    Enum { variants: Vec<Value> },
+    /// The instance was numerically smaller than the schema's `minimum`, or,
+    /// when `exclusive`, not strictly greater than it.
+    Minimum {
+        limit: Number,
+        found: Number,
+        exclusive: bool,
+    },
+    /// The instance was numerically larger than the schema's `maximum`, or,
+    /// when `exclusive`, not strictly less than it.
+    Maximum {
+        limit: Number,
+        found: Number,
+        exclusive: bool,
+    },
+    /// The schema was the literal `false` schema, which rejects everything.
+    False,
+    /// The instance's primitive type was not one of the schema's `type`.
+    Type { expected: Vec<String> },
+    /// The instance did not match any of the schema's `anyOf` subschemas.
+    AnyOf,
+    /// The instance matched a number of `oneOf` subschemas other than one.
+    OneOf { matched: usize },
+    /// The instance matched the schema's `not` subschema.
+    Not,
+    /// Following `$ref`s to validate this instance recursed past
+    /// [`MAX_REF_DEPTH`], which almost always means the schema refers to
+    /// itself with nothing in the instance to bound the recursion.
+    RefDepthExceeded,
+}
+
+impl fmt::Display for ValidationErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationErrorKind::Const { expected } => {
+                write!(f, "expected value to equal {}", expected)
+            }
+            ValidationErrorKind::Enum { variants } => {
+                write!(f, "expected value to be one of {:?}", variants)
+            }
+            ValidationErrorKind::Minimum {
+                limit,
+                found,
+                exclusive: true,
+            } => write!(f, "{} is not strictly greater than the minimum of {}", found, limit),
+            ValidationErrorKind::Minimum {
+                limit,
+                found,
+                exclusive: false,
+            } => write!(f, "{} is less than the minimum of {}", found, limit),
+            ValidationErrorKind::Maximum {
+                limit,
+                found,
+                exclusive: true,
+            } => write!(f, "{} is not strictly less than the maximum of {}", found, limit),
+            ValidationErrorKind::Maximum {
+                limit,
+                found,
+                exclusive: false,
+            } => write!(f, "{} is greater than the maximum of {}", found, limit),
+            ValidationErrorKind::False => {
+                write!(f, "the schema is `false`, which rejects all instances")
+            }
+            ValidationErrorKind::Type { expected } => {
+                write!(f, "expected type to be one of {:?}", expected)
+            }
+            ValidationErrorKind::AnyOf => {
+                write!(f, "value did not match any subschema in anyOf")
+            }
+            ValidationErrorKind::OneOf { matched } => write!(
+                f,
+                "value matched {} subschemas in oneOf, expected exactly 1",
+                matched
+            ),
+            ValidationErrorKind::Not => write!(f, "value matched the subschema in not"),
+            ValidationErrorKind::RefDepthExceeded => write!(
+                f,
+                "$ref chain exceeded the maximum depth of {} (likely a self-referential schema)",
+                MAX_REF_DEPTH
+            ),
+        }
+    }
+}
+
+/// One entry in a [`BasicOutput`] report: a single keyword evaluated against
+/// a single location in the instance.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BasicOutputUnit {
+    pub valid: bool,
+    pub keyword_location: String,
+    pub instance_location: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annotation: Option<Value>,
+}
+
+/// A value produced by a keyword that doesn't constrain validation but
+/// reports something about the instance when it runs (e.g. `default`).
+#[derive(Debug)]
+pub struct Annotation {
+    pub value: Value,
+    /// JSON pointer into the schema, from the document root.
+    pub schema_path: Vec<String>,
+}
+
+/// JSON Schema's standardized "basic" output format: an overall pass/fail
+/// flag plus a flat list of per-keyword units.
+#[derive(Debug, Serialize)]
+pub struct BasicOutput {
+    pub valid: bool,
+    pub errors: Vec<BasicOutputUnit>,
+}
+
+/// Render path segments as an RFC 6901 JSON pointer.
+fn json_pointer(segments: &[String]) -> String {
+    let mut pointer = String::new();
+    for segment in segments {
+        pointer.push('/');
+        pointer.push_str(&segment.replace('~', "~0").replace('/', "~1"));
+    }
+    pointer
+}
+
+/// A single keyword failure, with enough context to point back at both the
+/// offending part of the instance and the keyword that rejected it.
+#[derive(Debug)]
+pub struct ValidationError<'a> {
+    pub instance: Cow<'a, Value>,
+    pub kind: ValidationErrorKind,
+    /// JSON pointer into the instance, from the document root.
+    pub instance_path: Vec<String>,
+    /// JSON pointer into the schema, from the document root.
+    pub schema_path: Vec<String>,
+}
 
 pub trait Validator: std::fmt::Debug {
-    fn validate(&self, value: &Value) -> bool;
+    /// Validate `instance`, yielding one [`ValidationError`] per keyword
+    /// failure. An empty iterator means the instance is valid.
+    fn validate<'a>(&self, instance: &'a Value) -> Box<dyn Iterator<Item = ValidationError<'a>> + 'a>;
+
+    /// Cheap yes/no check that stops at the first failure instead of
+    /// collecting every error. The default implementation still builds that
+    /// first [`ValidationError`].
+    fn is_valid(&self, instance: &Value) -> bool {
+        self.validate(instance).next().is_none()
+    }
+
+    /// Collect [`Annotation`]s produced by keywords that don't constrain
+    /// validation but report something about `instance` regardless (e.g.
+    /// `default`). Most validators never produce any.
+    fn annotate(&self, _instance: &Value) -> Vec<Annotation> {
+        vec![]
+    }
+
+    /// Validate `instance`, rendering the result as JSON Schema's
+    /// standardized "basic" output format: one unit per keyword failure,
+    /// plus one per annotation produced along the way.
+    fn validate_verbose(&self, instance: &Value) -> BasicOutput {
+        let mut units: Vec<BasicOutputUnit> = self
+            .validate(instance)
+            .map(|error| BasicOutputUnit {
+                valid: false,
+                keyword_location: json_pointer(&error.schema_path),
+                instance_location: json_pointer(&error.instance_path),
+                error: Some(error.kind.to_string()),
+                annotation: None,
+            })
+            .collect();
+
+        units.extend(self.annotate(instance).into_iter().map(|annotation| BasicOutputUnit {
+            valid: true,
+            keyword_location: json_pointer(&annotation.schema_path),
+            instance_location: json_pointer(&[]),
+            error: None,
+            annotation: Some(annotation.value),
+        }));
+
+        BasicOutput {
+            valid: units.iter().all(|unit| unit.valid),
+            errors: units,
+        }
+    }
 }
 
 #[derive(Debug)]
 struct TrueValidator;
 impl Validator for TrueValidator {
-    fn validate(&self, _value: &Value) -> bool {
-        true
+    fn validate<'a>(&self, _instance: &'a Value) -> Box<dyn Iterator<Item = ValidationError<'a>> + 'a> {
+        Box::new(std::iter::empty())
     }
 }
 
 #[derive(Debug)]
 struct FalseValidator;
 impl Validator for FalseValidator {
-    fn validate(&self, _value: &Value) -> bool {
-        false
+    fn validate<'a>(&self, instance: &'a Value) -> Box<dyn Iterator<Item = ValidationError<'a>> + 'a> {
+        Box::new(std::iter::once(ValidationError {
+            instance: Cow::Borrowed(instance),
+            kind: ValidationErrorKind::False,
+            instance_path: vec![],
+            schema_path: vec![],
+        }))
     }
 }
 
 #[derive(Debug)]
 struct ConstValidator {
     value: Value,
+    schema_path: Vec<String>,
 }
 impl Validator for ConstValidator {
-    fn validate(&self, value: &Value) -> bool {
-        &self.value == value
+    fn validate<'a>(&self, instance: &'a Value) -> Box<dyn Iterator<Item = ValidationError<'a>> + 'a> {
+        if &self.value == instance {
+            Box::new(std::iter::empty())
+        } else {
+            Box::new(std::iter::once(ValidationError {
+                instance: Cow::Borrowed(instance),
+                kind: ValidationErrorKind::Const {
+                    expected: self.value.clone(),
+                },
+                instance_path: vec![],
+                schema_path: self.schema_path.clone(),
+            }))
+        }
+    }
+}
+
+/// `default` never constrains validation; it just reports its value as an
+/// [`Annotation`] whenever the keyword is present.
+#[derive(Debug)]
+struct DefaultValidator {
+    value: Value,
+}
+impl Validator for DefaultValidator {
+    fn validate<'a>(&self, _instance: &'a Value) -> Box<dyn Iterator<Item = ValidationError<'a>> + 'a> {
+        Box::new(std::iter::empty())
+    }
+
+    fn annotate(&self, _instance: &Value) -> Vec<Annotation> {
+        vec![Annotation {
+            value: self.value.clone(),
+            schema_path: vec!["default".to_string()],
+        }]
     }
 }
 
 #[derive(Debug)]
 struct EnumValidator {
+    variants: Vec<Value>,
     validators: Vec<Box<dyn Validator>>,
+    schema_path: Vec<String>,
 }
 impl Validator for EnumValidator {
-    fn validate(&self, value: &Value) -> bool {
-        self.validators.iter().any(|v| v.validate(value))
+    fn validate<'a>(&self, instance: &'a Value) -> Box<dyn Iterator<Item = ValidationError<'a>> + 'a> {
+        if self.validators.iter().any(|v| v.is_valid(instance)) {
+            Box::new(std::iter::empty())
+        } else {
+            Box::new(std::iter::once(ValidationError {
+                instance: Cow::Borrowed(instance),
+                kind: ValidationErrorKind::Enum {
+                    variants: self.variants.clone(),
+                },
+                instance_path: vec![],
+                schema_path: self.schema_path.clone(),
+            }))
+        }
+    }
+}
+
+/// A `serde_json::Number` decomposed into a representation that can be
+/// compared without a lossy cast: exact integers stay exact, and only
+/// genuine floats fall back to floating-point comparison.
+enum NumberRepr {
+    Int(i128),
+    Float(f64),
+}
+
+fn number_repr(n: &Number) -> NumberRepr {
+    if let Some(u) = n.as_u64() {
+        NumberRepr::Int(u as i128)
+    } else if let Some(i) = n.as_i64() {
+        NumberRepr::Int(i as i128)
+    } else {
+        NumberRepr::Float(n.as_f64().unwrap())
+    }
+}
+
+fn cmp_int_float(n: i128, f: f64) -> Option<Ordering> {
+    if f.is_nan() {
+        return None;
+    }
+    if f > i128::MAX as f64 {
+        return Some(Ordering::Less);
+    }
+    if f < i128::MIN as f64 {
+        return Some(Ordering::Greater);
+    }
+
+    let trunc = f.trunc();
+    match n.cmp(&(trunc as i128)) {
+        Ordering::Equal => {
+            let frac = f - trunc;
+            Some(if frac > 0.0 {
+                Ordering::Less
+            } else if frac < 0.0 {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            })
+        }
+        other => Some(other),
+    }
+}
+
+/// Compare two JSON numbers exactly, without routing integers through
+/// `as_f64()` first. Returns `None` if the comparison is undefined (i.e. one
+/// side is NaN).
+fn compare_numbers(a: &Number, b: &Number) -> Option<Ordering> {
+    match (number_repr(a), number_repr(b)) {
+        (NumberRepr::Int(a), NumberRepr::Int(b)) => Some(a.cmp(&b)),
+        (NumberRepr::Int(a), NumberRepr::Float(b)) => cmp_int_float(a, b),
+        (NumberRepr::Float(a), NumberRepr::Int(b)) => cmp_int_float(b, a).map(Ordering::reverse),
+        (NumberRepr::Float(a), NumberRepr::Float(b)) => a.partial_cmp(&b),
     }
 }
 
 #[derive(Debug)]
 struct MinimumValidator {
     value: Number,
+    /// `true` for `exclusiveMinimum` (strictly greater than), `false` for
+    /// plain `minimum` (greater than or equal to).
+    exclusive: bool,
+    schema_path: Vec<String>,
 }
 impl Validator for MinimumValidator {
-    fn validate(&self, value: &Value) -> bool {
-        if let Value::Number(num) = value {
-            if let (Some(n1), Some(n2)) = (self.value.as_f64(), num.as_f64()) {
-                n1 <= n2
+    fn validate<'a>(&self, instance: &'a Value) -> Box<dyn Iterator<Item = ValidationError<'a>> + 'a> {
+        if let Value::Number(num) = instance {
+            let ordering = compare_numbers(num, &self.value);
+            let valid = if self.exclusive {
+                matches!(ordering, Some(Ordering::Greater))
+            } else {
+                matches!(ordering, Some(Ordering::Greater) | Some(Ordering::Equal))
+            };
+
+            if valid {
+                Box::new(std::iter::empty())
+            } else {
+                Box::new(std::iter::once(ValidationError {
+                    instance: Cow::Borrowed(instance),
+                    kind: ValidationErrorKind::Minimum {
+                        limit: self.value.clone(),
+                        found: num.clone(),
+                        exclusive: self.exclusive,
+                    },
+                    instance_path: vec![],
+                    schema_path: self.schema_path.clone(),
+                }))
+            }
+        } else {
+            Box::new(std::iter::empty())
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MaximumValidator {
+    value: Number,
+    /// `true` for `exclusiveMaximum` (strictly less than), `false` for
+    /// plain `maximum` (less than or equal to).
+    exclusive: bool,
+    schema_path: Vec<String>,
+}
+impl Validator for MaximumValidator {
+    fn validate<'a>(&self, instance: &'a Value) -> Box<dyn Iterator<Item = ValidationError<'a>> + 'a> {
+        if let Value::Number(num) = instance {
+            let ordering = compare_numbers(num, &self.value);
+            let valid = if self.exclusive {
+                matches!(ordering, Some(Ordering::Less))
+            } else {
+                matches!(ordering, Some(Ordering::Less) | Some(Ordering::Equal))
+            };
+
+            if valid {
+                Box::new(std::iter::empty())
             } else {
-                false
+                Box::new(std::iter::once(ValidationError {
+                    instance: Cow::Borrowed(instance),
+                    kind: ValidationErrorKind::Maximum {
+                        limit: self.value.clone(),
+                        found: num.clone(),
+                        exclusive: self.exclusive,
+                    },
+                    instance_path: vec![],
+                    schema_path: self.schema_path.clone(),
+                }))
             }
         } else {
-            true
+            Box::new(std::iter::empty())
         }
     }
 }
 
-pub fn generate_validator(schema: &Value) -> Result<Box<dyn Validator>, &Value> {
+const TYPE_NULL: u8 = 1 << 0;
+const TYPE_BOOLEAN: u8 = 1 << 1;
+const TYPE_STRING: u8 = 1 << 2;
+const TYPE_NUMBER: u8 = 1 << 3;
+const TYPE_INTEGER: u8 = 1 << 4;
+const TYPE_ARRAY: u8 = 1 << 5;
+const TYPE_OBJECT: u8 = 1 << 6;
+
+fn type_bit(name: &str) -> Option<u8> {
+    match name {
+        "null" => Some(TYPE_NULL),
+        "boolean" => Some(TYPE_BOOLEAN),
+        "string" => Some(TYPE_STRING),
+        "number" => Some(TYPE_NUMBER),
+        "integer" => Some(TYPE_INTEGER),
+        "array" => Some(TYPE_ARRAY),
+        "object" => Some(TYPE_OBJECT),
+        _ => None,
+    }
+}
+
+fn is_integer(num: &Number) -> bool {
+    num.as_i64().is_some() || num.as_u64().is_some() || num.as_f64().is_some_and(|f| f.trunc() == f)
+}
+
+/// One bit per primitive type, so membership testing is a single mask check.
+#[derive(Debug, Clone, Copy)]
+struct TypeSet(u8);
+
+impl TypeSet {
+    fn from_names(names: &[String]) -> Result<Self, Value> {
+        names.iter().try_fold(0, |mask, name| {
+            type_bit(name)
+                .map(|bit| mask | bit)
+                .ok_or_else(|| Value::String(format!("unknown type name '{}'", name)))
+        }).map(TypeSet)
+    }
+
+    fn contains(&self, bit: u8) -> bool {
+        self.0 & bit != 0
+    }
+
+    fn matches(&self, instance: &Value) -> bool {
+        match instance {
+            Value::Null => self.contains(TYPE_NULL),
+            Value::Bool(_) => self.contains(TYPE_BOOLEAN),
+            Value::String(_) => self.contains(TYPE_STRING),
+            Value::Array(_) => self.contains(TYPE_ARRAY),
+            Value::Object(_) => self.contains(TYPE_OBJECT),
+            Value::Number(num) => {
+                self.contains(TYPE_NUMBER) || (self.contains(TYPE_INTEGER) && is_integer(num))
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TypeValidator {
+    set: TypeSet,
+    names: Vec<String>,
+    schema_path: Vec<String>,
+}
+impl Validator for TypeValidator {
+    fn validate<'a>(&self, instance: &'a Value) -> Box<dyn Iterator<Item = ValidationError<'a>> + 'a> {
+        if self.set.matches(instance) {
+            Box::new(std::iter::empty())
+        } else {
+            Box::new(std::iter::once(ValidationError {
+                instance: Cow::Borrowed(instance),
+                kind: ValidationErrorKind::Type {
+                    expected: self.names.clone(),
+                },
+                instance_path: vec![],
+                schema_path: self.schema_path.clone(),
+            }))
+        }
+    }
+}
+
+/// Implicit conjunction: requires every child validator to pass. Used both
+/// to compile a schema object's keywords together and for the explicit
+/// `allOf` keyword. Each child carries a `schema_path` prefix that is
+/// prepended to whatever path its own errors already carry, so nested
+/// `allOf` subschemas still point back at e.g. `allOf/0/type`. Children are
+/// held behind `Rc` rather than `Box` so `validate` can chain their error
+/// iterators lazily (cloning a handle, not borrowing `self`), letting
+/// `is_valid`'s default `next().is_none()` short-circuit on the first
+/// failing child instead of evaluating every keyword up front.
+#[derive(Debug)]
+struct AllOfValidator {
+    children: Vec<(Vec<String>, Rc<dyn Validator>)>,
+}
+impl Validator for AllOfValidator {
+    fn validate<'a>(&self, instance: &'a Value) -> Box<dyn Iterator<Item = ValidationError<'a>> + 'a> {
+        Box::new(self.children.clone().into_iter().flat_map(move |(prefix, validator)| {
+            validator.validate(instance).map(move |mut error| {
+                if !prefix.is_empty() {
+                    error.schema_path = prefix.iter().cloned().chain(error.schema_path).collect();
+                }
+                error
+            })
+        }))
+    }
+
+    fn annotate(&self, instance: &Value) -> Vec<Annotation> {
+        self.children
+            .iter()
+            .flat_map(|(prefix, validator)| {
+                validator.annotate(instance).into_iter().map(move |mut annotation| {
+                    if !prefix.is_empty() {
+                        annotation.schema_path =
+                            prefix.iter().cloned().chain(annotation.schema_path).collect();
+                    }
+                    annotation
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+struct AnyOfValidator {
+    validators: Vec<Box<dyn Validator>>,
+    schema_path: Vec<String>,
+}
+impl Validator for AnyOfValidator {
+    fn validate<'a>(&self, instance: &'a Value) -> Box<dyn Iterator<Item = ValidationError<'a>> + 'a> {
+        if self.validators.iter().any(|v| v.is_valid(instance)) {
+            Box::new(std::iter::empty())
+        } else {
+            Box::new(std::iter::once(ValidationError {
+                instance: Cow::Borrowed(instance),
+                kind: ValidationErrorKind::AnyOf,
+                instance_path: vec![],
+                schema_path: self.schema_path.clone(),
+            }))
+        }
+    }
+}
+
+#[derive(Debug)]
+struct OneOfValidator {
+    validators: Vec<Box<dyn Validator>>,
+    schema_path: Vec<String>,
+}
+impl Validator for OneOfValidator {
+    fn validate<'a>(&self, instance: &'a Value) -> Box<dyn Iterator<Item = ValidationError<'a>> + 'a> {
+        let matched = self.validators.iter().filter(|v| v.is_valid(instance)).count();
+        if matched == 1 {
+            Box::new(std::iter::empty())
+        } else {
+            Box::new(std::iter::once(ValidationError {
+                instance: Cow::Borrowed(instance),
+                kind: ValidationErrorKind::OneOf { matched },
+                instance_path: vec![],
+                schema_path: self.schema_path.clone(),
+            }))
+        }
+    }
+}
+
+#[derive(Debug)]
+struct NotValidator {
+    validator: Box<dyn Validator>,
+    schema_path: Vec<String>,
+}
+impl Validator for NotValidator {
+    fn validate<'a>(&self, instance: &'a Value) -> Box<dyn Iterator<Item = ValidationError<'a>> + 'a> {
+        if self.validator.is_valid(instance) {
+            Box::new(std::iter::once(ValidationError {
+                instance: Cow::Borrowed(instance),
+                kind: ValidationErrorKind::Not,
+                instance_path: vec![],
+                schema_path: self.schema_path.clone(),
+            }))
+        } else {
+            Box::new(std::iter::empty())
+        }
+    }
+}
+
+/// Fetches the schema document an external (non-fragment) `$ref` points at.
+/// The default resolver used by [`generate_validator`] resolves nothing,
+/// so only local `#/...` references work out of the box.
+pub trait Resolver: std::fmt::Debug {
+    fn resolve(&self, uri: &Url) -> Option<Value>;
+}
+
+#[derive(Debug)]
+struct NullResolver;
+impl Resolver for NullResolver {
+    fn resolve(&self, _uri: &Url) -> Option<Value> {
+        None
+    }
+}
+
+/// A `$ref` target: `None` while still compiling (lets cycles resolve
+/// against the same handle instead of recursing forever), `Some` once done.
+type RefTarget = Rc<RefCell<Option<Box<dyn Validator>>>>;
+
+/// Shared compilation state: which document is being compiled and from
+/// where, plus a cache of `$ref` targets keyed by resolved URI so that
+/// recursive schemas don't recompile (or infinitely recurse).
+struct CompilationContext<'a> {
+    base_uri: Url,
+    root: &'a Value,
+    resolver: &'a dyn Resolver,
+    cache: Rc<RefCell<HashMap<String, RefTarget>>>,
+    draft: Draft,
+}
+
+/// Resolves a `/`-separated, `~`-escaped RFC 6901 JSON pointer against a
+/// document. An empty pointer resolves to the document root.
+fn resolve_pointer<'a>(root: &'a Value, pointer: &str) -> Option<&'a Value> {
+    let pointer = pointer.strip_prefix('/').unwrap_or(pointer);
+    if pointer.is_empty() {
+        return Some(root);
+    }
+
+    pointer.split('/').try_fold(root, |value, segment| {
+        let segment = segment.replace("~1", "/").replace("~0", "~");
+        match value {
+            Value::Object(obj) => obj.get(&segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        }
+    })
+}
+
+/// How many nested `$ref` dereferences [`RefValidator::validate`] will
+/// follow before giving up. `RefTarget`'s `RefCell` only breaks the cycle at
+/// *compile* time -- a schema that refers to itself (e.g. a linked list with
+/// no `items`/`properties` validator to consume a level of instance nesting)
+/// would otherwise recurse once per `$ref` hop forever and blow the stack.
+/// This caps it at a depth no legitimate schema should ever reach.
+const MAX_REF_DEPTH: u32 = 128;
+
+thread_local! {
+    static REF_DEPTH: RefCell<u32> = const { RefCell::new(0) };
+}
+
+/// Holds a `$ref`'s resolved target, populated once compilation of that
+/// target finishes. Recursive schemas see the cell while it's still empty
+/// and defer to it rather than recompiling, breaking the cycle.
+#[derive(Debug)]
+struct RefValidator {
+    target: RefTarget,
+}
+impl Validator for RefValidator {
+    fn validate<'a>(&self, instance: &'a Value) -> Box<dyn Iterator<Item = ValidationError<'a>> + 'a> {
+        let depth = REF_DEPTH.with(|depth| {
+            *depth.borrow_mut() += 1;
+            *depth.borrow()
+        });
+        if depth > MAX_REF_DEPTH {
+            REF_DEPTH.with(|depth| *depth.borrow_mut() -= 1);
+            return Box::new(std::iter::once(ValidationError {
+                instance: Cow::Borrowed(instance),
+                kind: ValidationErrorKind::RefDepthExceeded,
+                instance_path: vec![],
+                schema_path: vec!["$ref".to_string()],
+            }));
+        }
+
+        let target = self.target.borrow();
+        let validator = target
+            .as_ref()
+            .expect("$ref target finishes compiling before validate() can run");
+        let errors: Vec<_> = validator
+            .validate(instance)
+            .map(|mut error| {
+                error.schema_path.insert(0, "$ref".to_string());
+                error
+            })
+            .collect();
+        REF_DEPTH.with(|depth| *depth.borrow_mut() -= 1);
+        Box::new(errors.into_iter())
+    }
+}
+
+/// Compiles a `$ref` into a [`RefValidator`]. Self-referential schemas
+/// (directly or through a chain of `$ref`s) compile successfully -- the
+/// `RefTarget` cell breaks the cycle -- and validate successfully too, up to
+/// [`MAX_REF_DEPTH`] nested dereferences, at which point `validate` reports
+/// [`ValidationErrorKind::RefDepthExceeded`] instead of recursing forever.
+fn compile_ref(ref_str: &str, ctx: &CompilationContext) -> Result<Box<dyn Validator>, Value> {
+    let (doc_uri, fragment) = match ref_str.split_once('#') {
+        Some((before, frag)) => (before, frag),
+        None => (ref_str, ""),
+    };
+
+    let (target_uri, target_root) = if doc_uri.is_empty() {
+        (ctx.base_uri.clone(), Cow::Borrowed(ctx.root))
+    } else {
+        let resolved = ctx
+            .base_uri
+            .join(doc_uri)
+            .map_err(|_| Value::String(format!("invalid $ref '{}'", ref_str)))?;
+        let document = ctx
+            .resolver
+            .resolve(&resolved)
+            .ok_or_else(|| Value::String(format!("could not resolve $ref '{}'", ref_str)))?;
+        (resolved, Cow::Owned(document))
+    };
+
+    let cache_key = format!("{}#{}", target_uri, fragment);
+
+    if let Some(cell) = ctx.cache.borrow().get(&cache_key) {
+        return Ok(Box::new(RefValidator {
+            target: cell.clone(),
+        }));
+    }
+
+    let cell = Rc::new(RefCell::new(None));
+    ctx.cache.borrow_mut().insert(cache_key, cell.clone());
+
+    let target_schema = resolve_pointer(&target_root, fragment)
+        .ok_or_else(|| Value::String(format!("could not resolve $ref '{}'", ref_str)))?;
+
+    let child_ctx = CompilationContext {
+        base_uri: target_uri,
+        root: &target_root,
+        resolver: ctx.resolver,
+        cache: ctx.cache.clone(),
+        draft: ctx.draft,
+    };
+    let compiled = compile(target_schema, &child_ctx)?;
+    *cell.borrow_mut() = Some(compiled);
+
+    Ok(Box::new(RefValidator { target: cell }))
+}
+
+/// Compile a `Value::Array` of subschemas, as used by `allOf`/`anyOf`/`oneOf`.
+fn compile_subschemas(val: &Value, ctx: &CompilationContext) -> Result<Vec<Box<dyn Validator>>, Value> {
+    assert_matches!(val, Value::Array(_));
+    if let Value::Array(items) = val {
+        items.iter().map(|item| compile(item, ctx)).collect()
+    } else {
+        unreachable!()
+    }
+}
+
+/// Keywords `compile` actually turns into a validator (including ones like
+/// `default` that never reject an instance but still produce an
+/// [`Annotation`]).
+const VALIDATING_KEYWORDS: &[&str] = &[
+    "$ref",
+    "const",
+    "enum",
+    "minimum",
+    "exclusiveMinimum",
+    "maximum",
+    "exclusiveMaximum",
+    "type",
+    "allOf",
+    "anyOf",
+    "oneOf",
+    "not",
+    "default",
+];
+
+/// Keywords `compile` accepts and ignores rather than compiling them into a
+/// validator. None of them is surfaced as an [`Annotation`]. `definitions`
+/// is only reached through a `$ref`, and `$id` does not change the base URI
+/// that `$ref`s resolve against.
+const ANNOTATION_KEYWORDS: &[&str] = &["$schema", "$id", "$comment", "title", "description", "examples", "definitions"];
+
+/// Whether `compile` has an opinion about `keyword`, one way or another.
+/// Anything else reaching `compile` is a keyword this crate doesn't
+/// implement yet, and `compile` must fail loudly rather than silently
+/// compiling it away into an always-valid schema.
+fn is_known_keyword(keyword: &str) -> bool {
+    VALIDATING_KEYWORDS.contains(&keyword) || ANNOTATION_KEYWORDS.contains(&keyword)
+}
+
+fn compile(schema: &Value, ctx: &CompilationContext) -> Result<Box<dyn Validator>, Value> {
     match schema {
         Value::Object(obj) => {
+            if let Some(Value::String(ref_str)) = obj.get("$ref") {
+                return compile_ref(ref_str, ctx);
+            }
+
+            if let Some(keyword) = obj.keys().find(|k| !is_known_keyword(k)) {
+                return Err(Value::String(format!("unimplemented keyword '{}'", keyword)));
+            }
+
+            let mut children: Vec<(Vec<String>, Rc<dyn Validator>)> = vec![];
+
             if let Some(val) = obj.get("const") {
-                assert!(obj.len() == 1);
-                return Ok(Box::new(ConstValidator { value: val.clone() }));
+                children.push((
+                    vec![],
+                    Rc::new(ConstValidator {
+                        value: val.clone(),
+                        schema_path: vec!["const".to_string()],
+                    }),
+                ));
+            }
+
+            if let Some(val) = obj.get("default") {
+                children.push((vec![], Rc::new(DefaultValidator { value: val.clone() })));
             }
 
             if let Some(val) = obj.get("enum") {
-                assert!(obj.len() == 1);
                 assert_matches!(val, Value::Array(_));
 
                 if let Value::Array(items) = val {
-                    let validators = items
+                    let variant_validators = items
                         .iter()
-                        .map(|val| {
-                            Box::new(ConstValidator { value: val.clone() }) as Box<dyn Validator>
+                        .enumerate()
+                        .map(|(i, val)| {
+                            Box::new(ConstValidator {
+                                value: val.clone(),
+                                schema_path: vec!["enum".to_string(), i.to_string()],
+                            }) as Box<dyn Validator>
                         })
                         .collect();
-                    return Ok(Box::new(EnumValidator { validators }));
+                    children.push((
+                        vec![],
+                        Rc::new(EnumValidator {
+                            variants: items.clone(),
+                            validators: variant_validators,
+                            schema_path: vec!["enum".to_string()],
+                        }),
+                    ));
                 }
             }
 
-            if let Some(val) = obj.get("minimum") {
-                assert!(obj.len() == 1);
-                assert_matches!(val, Value::Number(_));
-                if let Value::Number(val) = val {
-                    return Ok(Box::new(MinimumValidator {
-                        value: val.clone(),
-                    }));
+            // `exclusiveMinimum` diverges between dialects: draft-4 treats it as a
+            // boolean modifier on `minimum`, draft-6+ as its own numeric keyword.
+            if ctx.draft == Draft::Draft4 {
+                if let Some(val) = obj.get("minimum") {
+                    let Value::Number(val) = val else {
+                        return Err(Value::String("'minimum' must be a number".to_string()));
+                    };
+                    let exclusive = matches!(obj.get("exclusiveMinimum"), Some(Value::Bool(true)));
+                    children.push((
+                        vec![],
+                        Rc::new(MinimumValidator {
+                            value: val.clone(),
+                            exclusive,
+                            schema_path: vec!["minimum".to_string()],
+                        }),
+                    ));
+                }
+            } else {
+                if let Some(val) = obj.get("minimum") {
+                    let Value::Number(val) = val else {
+                        return Err(Value::String("'minimum' must be a number".to_string()));
+                    };
+                    children.push((
+                        vec![],
+                        Rc::new(MinimumValidator {
+                            value: val.clone(),
+                            exclusive: false,
+                            schema_path: vec!["minimum".to_string()],
+                        }),
+                    ));
+                }
+
+                if let Some(val) = obj.get("exclusiveMinimum") {
+                    let Value::Number(val) = val else {
+                        return Err(Value::String(
+                            "'exclusiveMinimum' must be a number in this draft".to_string(),
+                        ));
+                    };
+                    children.push((
+                        vec![],
+                        Rc::new(MinimumValidator {
+                            value: val.clone(),
+                            exclusive: true,
+                            schema_path: vec!["exclusiveMinimum".to_string()],
+                        }),
+                    ));
+                }
+            }
+
+            // `exclusiveMaximum` diverges between dialects the same way
+            // `exclusiveMinimum` does: draft-4 boolean modifier on `maximum`,
+            // draft-6+ standalone numeric keyword.
+            if ctx.draft == Draft::Draft4 {
+                if let Some(val) = obj.get("maximum") {
+                    let Value::Number(val) = val else {
+                        return Err(Value::String("'maximum' must be a number".to_string()));
+                    };
+                    let exclusive = matches!(obj.get("exclusiveMaximum"), Some(Value::Bool(true)));
+                    children.push((
+                        vec![],
+                        Rc::new(MaximumValidator {
+                            value: val.clone(),
+                            exclusive,
+                            schema_path: vec!["maximum".to_string()],
+                        }),
+                    ));
+                }
+            } else {
+                if let Some(val) = obj.get("maximum") {
+                    let Value::Number(val) = val else {
+                        return Err(Value::String("'maximum' must be a number".to_string()));
+                    };
+                    children.push((
+                        vec![],
+                        Rc::new(MaximumValidator {
+                            value: val.clone(),
+                            exclusive: false,
+                            schema_path: vec!["maximum".to_string()],
+                        }),
+                    ));
+                }
+
+                if let Some(val) = obj.get("exclusiveMaximum") {
+                    let Value::Number(val) = val else {
+                        return Err(Value::String(
+                            "'exclusiveMaximum' must be a number in this draft".to_string(),
+                        ));
+                    };
+                    children.push((
+                        vec![],
+                        Rc::new(MaximumValidator {
+                            value: val.clone(),
+                            exclusive: true,
+                            schema_path: vec!["exclusiveMaximum".to_string()],
+                        }),
+                    ));
                 }
             }
 
             if let Some(val) = obj.get("type") {
-                assert_matches!(val, Value::String(_));
-                return Err(schema);
+                let names: Vec<String> = match val {
+                    Value::String(name) => vec![name.clone()],
+                    Value::Array(items) => items
+                        .iter()
+                        .map(|item| match item {
+                            Value::String(name) => Ok(name.clone()),
+                            _ => Err(Value::String(
+                                "'type' array must contain only strings".to_string(),
+                            )),
+                        })
+                        .collect::<Result<Vec<String>, Value>>()?,
+                    _ => {
+                        return Err(Value::String(
+                            "'type' must be a string or an array of strings".to_string(),
+                        ))
+                    }
+                };
+                children.push((
+                    vec![],
+                    Rc::new(TypeValidator {
+                        set: TypeSet::from_names(&names)?,
+                        names,
+                        schema_path: vec!["type".to_string()],
+                    }),
+                ));
             }
 
-            Ok(Box::new(ConstValidator {
-                value: schema.clone(),
-            }))
+            if let Some(val) = obj.get("allOf") {
+                let nested = compile_subschemas(val, ctx)?
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, v)| (vec!["allOf".to_string(), i.to_string()], Rc::from(v)))
+                    .collect();
+                children.push((vec![], Rc::new(AllOfValidator { children: nested })));
+            }
+
+            if let Some(val) = obj.get("anyOf") {
+                children.push((
+                    vec![],
+                    Rc::new(AnyOfValidator {
+                        validators: compile_subschemas(val, ctx)?,
+                        schema_path: vec!["anyOf".to_string()],
+                    }),
+                ));
+            }
+
+            if let Some(val) = obj.get("oneOf") {
+                children.push((
+                    vec![],
+                    Rc::new(OneOfValidator {
+                        validators: compile_subschemas(val, ctx)?,
+                        schema_path: vec!["oneOf".to_string()],
+                    }),
+                ));
+            }
+
+            if let Some(val) = obj.get("not") {
+                children.push((
+                    vec![],
+                    Rc::new(NotValidator {
+                        validator: compile(val, ctx)?,
+                        schema_path: vec!["not".to_string()],
+                    }),
+                ));
+            }
+
+            Ok(Box::new(AllOfValidator { children }))
         }
 
         Value::Bool(val) => {
@@ -110,8 +1023,326 @@ pub fn generate_validator(schema: &Value) -> Result<Box<dyn Validator>, &Value>
             }
         }
 
-        _ => {
-            return Err(schema);
+        _ => Err(schema.clone()),
+    }
+}
+
+/// Which JSON Schema dialect to compile against. Keyword behavior that
+/// diverges between drafts (e.g. `exclusiveMinimum`) is gated on this.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Draft {
+    Draft4,
+    Draft6,
+    #[default]
+    Draft7,
+}
+
+/// Entry point for compiling a schema with a chosen [`Draft`] and an
+/// optional custom [`Resolver`] for external `$ref`s (see [`with_resolver`](
+/// CompilationOptions::with_resolver)). Without one, external `$ref`s (i.e.
+/// anything but a local `#/...` fragment) fail to compile.
+#[derive(Debug)]
+pub struct CompilationOptions {
+    draft: Draft,
+    resolver: Box<dyn Resolver>,
+}
+
+impl Default for CompilationOptions {
+    fn default() -> Self {
+        CompilationOptions {
+            draft: Draft::default(),
+            resolver: Box::new(NullResolver),
+        }
+    }
+}
+
+impl CompilationOptions {
+    pub fn new() -> Self {
+        CompilationOptions::default()
+    }
+
+    pub fn with_draft(mut self, draft: Draft) -> Self {
+        self.draft = draft;
+        self
+    }
+
+    /// Supply a [`Resolver`] so external (non-fragment) `$ref`s can be
+    /// fetched instead of failing to resolve.
+    pub fn with_resolver(mut self, resolver: Box<dyn Resolver>) -> Self {
+        self.resolver = resolver;
+        self
+    }
+
+    pub fn compile(&self, schema: &Value) -> Result<Box<dyn Validator>, Value> {
+        let ctx = CompilationContext {
+            base_uri: Url::parse("mem://root/").unwrap(),
+            root: schema,
+            resolver: self.resolver.as_ref(),
+            cache: Rc::new(RefCell::new(HashMap::new())),
+            draft: self.draft,
+        };
+        compile(schema, &ctx)
+    }
+}
+
+pub fn generate_validator(schema: &Value) -> Result<Box<dyn Validator>, Value> {
+    CompilationOptions::new().compile(schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_derive::Deserialize;
+    use serde_json::Value;
+    use std::fs::File;
+
+    #[derive(Deserialize)]
+    struct SchemaTest {
+        description: String,
+        schema: Value,
+        tests: Vec<Test>,
+    }
+
+    #[derive(Deserialize)]
+    struct Test {
+        description: String,
+        data: Value,
+        valid: bool,
+    }
+
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct TestFailures {
+        description: String,
+        passes: Vec<String>,
+        failures: Vec<String>,
+    }
+
+    fn read_tests(file: &str) -> Vec<SchemaTest> {
+        serde_json::from_reader(File::open(file).unwrap()).unwrap()
+    }
+
+    fn run_tests(file: &str) {
+        for schema_test in read_tests(file) {
+            let mut passes = vec![];
+            let mut failures = vec![];
+
+            match super::generate_validator(&schema_test.schema) {
+                Ok(validator) => {
+                    for test in schema_test.tests {
+                        if validator.is_valid(&test.data) != test.valid {
+                            failures.push(format!("FAILED {}", test.description));
+                        } else {
+                            passes.push(format!("PASSED {}", test.description));
+                        }
+                    }
+                }
+                Err(value) => {
+                    failures.push(format!("UNIMPLEMENTED '{}'", value));
+                }
+            }
+
+            if !failures.is_empty() {
+                panic!(
+                    "{:#?}",
+                    TestFailures {
+                        description: schema_test.description,
+                        passes,
+                        failures
+                    }
+                );
+            }
         }
     }
+
+    #[test]
+    fn test_additional_items() {
+        run_tests("test-suite/tests/draft7/additionalItems.json");
+    }
+
+    #[test]
+    fn test_additional_properties() {
+        run_tests("test-suite/tests/draft7/additionalProperties.json");
+    }
+
+    #[test]
+    fn test_all_of() {
+        run_tests("test-suite/tests/draft7/allOf.json");
+    }
+
+    #[test]
+    fn test_any_of() {
+        run_tests("test-suite/tests/draft7/anyOf.json");
+    }
+
+    #[test]
+    fn test_boolean_schema() {
+        run_tests("test-suite/tests/draft7/boolean_schema.json");
+    }
+
+    #[test]
+    fn test_const() {
+        run_tests("test-suite/tests/draft7/const.json");
+    }
+
+    #[test]
+    fn test_contains() {
+        run_tests("test-suite/tests/draft7/contains.json");
+    }
+
+    #[test]
+    fn test_default() {
+        run_tests("test-suite/tests/draft7/default.json");
+    }
+
+    #[test]
+    fn test_definitions() {
+        run_tests("test-suite/tests/draft7/definitions.json");
+    }
+
+    #[test]
+    fn test_dependencies() {
+        run_tests("test-suite/tests/draft7/dependencies.json");
+    }
+
+    #[test]
+    fn test_enum() {
+        run_tests("test-suite/tests/draft7/enum.json");
+    }
+
+    #[test]
+    fn test_exclusive_maximum() {
+        run_tests("test-suite/tests/draft7/exclusiveMaximum.json");
+    }
+
+    #[test]
+    fn test_exclusive_minimum() {
+        run_tests("test-suite/tests/draft7/exclusiveMinimum.json");
+    }
+
+    // There's no draft4 fixture directory to drive through `run_tests`, so
+    // this exercises the draft4 `exclusiveMinimum`-as-boolean-modifier
+    // behavior directly instead of leaving it untested.
+    #[test]
+    fn test_draft4_exclusive_minimum_is_a_boolean_modifier() {
+        use super::{CompilationOptions, Draft};
+
+        let schema = serde_json::json!({"minimum": 5, "exclusiveMinimum": true});
+        let validator = CompilationOptions::new()
+            .with_draft(Draft::Draft4)
+            .compile(&schema)
+            .unwrap();
+
+        assert!(!validator.is_valid(&serde_json::json!(5)));
+        assert!(validator.is_valid(&serde_json::json!(6)));
+    }
+
+    #[test]
+    fn test_if_then_else() {
+        run_tests("test-suite/tests/draft7/if-then-else.json");
+    }
+
+    #[test]
+    fn test_items() {
+        run_tests("test-suite/tests/draft7/items.json");
+    }
+
+    #[test]
+    fn test_maximum() {
+        run_tests("test-suite/tests/draft7/maximum.json");
+    }
+
+    #[test]
+    fn test_max_items() {
+        run_tests("test-suite/tests/draft7/maxItems.json");
+    }
+
+    #[test]
+    fn test_max_length() {
+        run_tests("test-suite/tests/draft7/maxLength.json");
+    }
+
+    #[test]
+    fn test_max_properties() {
+        run_tests("test-suite/tests/draft7/maxProperties.json");
+    }
+
+    #[test]
+    fn test_minimum() {
+        run_tests("test-suite/tests/draft7/minimum.json");
+    }
+
+    #[test]
+    fn test_min_items() {
+        run_tests("test-suite/tests/draft7/minItems.json");
+    }
+
+    #[test]
+    fn test_min_length() {
+        run_tests("test-suite/tests/draft7/minLength.json");
+    }
+
+    #[test]
+    fn test_min_properties() {
+        run_tests("test-suite/tests/draft7/minProperties.json");
+    }
+
+    #[test]
+    fn test_multiple_of() {
+        run_tests("test-suite/tests/draft7/multipleOf.json");
+    }
+
+    #[test]
+    fn test_not() {
+        run_tests("test-suite/tests/draft7/not.json");
+    }
+
+    #[test]
+    fn test_one_of() {
+        run_tests("test-suite/tests/draft7/oneOf.json");
+    }
+
+    #[test]
+    fn test_pattern() {
+        run_tests("test-suite/tests/draft7/pattern.json");
+    }
+
+    #[test]
+    fn test_pattern_properties() {
+        run_tests("test-suite/tests/draft7/patternProperties.json");
+    }
+
+    #[test]
+    fn test_properties() {
+        run_tests("test-suite/tests/draft7/properties.json");
+    }
+
+    #[test]
+    fn test_property_names() {
+        run_tests("test-suite/tests/draft7/propertyNames.json");
+    }
+
+    #[test]
+    fn test_ref() {
+        run_tests("test-suite/tests/draft7/ref.json");
+    }
+
+    #[test]
+    fn test_ref_remote() {
+        run_tests("test-suite/tests/draft7/refRemote.json");
+    }
+
+    #[test]
+    fn test_required() {
+        run_tests("test-suite/tests/draft7/required.json");
+    }
+
+    #[test]
+    fn test_type() {
+        run_tests("test-suite/tests/draft7/type.json");
+    }
+
+    #[test]
+    fn test_unique_items() {
+        run_tests("test-suite/tests/draft7/uniqueItems.json");
+    }
 }